@@ -33,11 +33,28 @@ pub struct Cmd {
     pub expr: Option<String>,
 }
 
-#[derive(EnumString, EnumVariantNames, IntoStaticStr, Debug)]
+#[derive(EnumString, EnumVariantNames, IntoStaticStr, Debug, Clone, Copy, PartialEq, Eq)]
 #[strum(serialize_all = "kebab_case")]
 pub enum Format {
     Auto,
     Json,
     Yaml,
     Toml,
+    Ron,
+    Cbor,
+}
+
+impl Format {
+    /// Maps a file extension (without the leading dot) to the format that
+    /// handles it, or `None` when the extension isn't recognized.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "ron" => Some(Self::Ron),
+            "cbor" => Some(Self::Cbor),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,85 @@
+use crate::prelude::*;
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum DmtError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Yaml(serde_yaml::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    Ron(ron::Error),
+    Cbor(serde_cbor::Error),
+    SelectorParse(SelectorParseError),
+}
+
+impl fmt::Display for DmtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Json(e) => write!(f, "json error: {}", e),
+            Self::Yaml(e) => write!(f, "yaml error: {}", e),
+            Self::TomlDe(e) => write!(f, "toml error: {}", e),
+            Self::TomlSer(e) => write!(f, "toml error: {}", e),
+            Self::Ron(e) => write!(f, "ron error: {}", e),
+            Self::Cbor(e) => write!(f, "cbor error: {}", e),
+            Self::SelectorParse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DmtError {}
+
+impl From<io::Error> for DmtError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for DmtError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<serde_yaml::Error> for DmtError {
+    fn from(e: serde_yaml::Error) -> Self {
+        Self::Yaml(e)
+    }
+}
+
+impl From<toml::de::Error> for DmtError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::TomlDe(e)
+    }
+}
+
+impl From<toml::ser::Error> for DmtError {
+    fn from(e: toml::ser::Error) -> Self {
+        Self::TomlSer(e)
+    }
+}
+
+impl From<ron::Error> for DmtError {
+    fn from(e: ron::Error) -> Self {
+        Self::Ron(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for DmtError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        Self::Ron(e.code)
+    }
+}
+
+impl From<serde_cbor::Error> for DmtError {
+    fn from(e: serde_cbor::Error) -> Self {
+        Self::Cbor(e)
+    }
+}
+
+impl From<SelectorParseError> for DmtError {
+    fn from(e: SelectorParseError) -> Self {
+        Self::SelectorParse(e)
+    }
+}
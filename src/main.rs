@@ -1,18 +1,20 @@
 #![allow(dead_code, unused_macros)]
 
 mod cli;
+mod error;
 mod selector;
 mod value;
 
 mod prelude {
     pub use crate::cli::*;
+    pub use crate::error::*;
     pub use crate::selector::*;
     pub use crate::value::*;
 }
 
 use crate::prelude::*;
 use structopt::*;
-use std::{fs, io, path::PathBuf};
+use std::{fs, io::{self, Read, Write}, path::PathBuf, process};
 
 fn get_input(input: Option<&PathBuf>) -> io::Result<Box<dyn io::BufRead>> {
     if let Some(path) = input {
@@ -25,47 +27,281 @@ fn get_input(input: Option<&PathBuf>) -> io::Result<Box<dyn io::BufRead>> {
 
 fn get_output(output: Option<&PathBuf>) -> io::Result<Box<dyn io::Write>> {
     if let Some(path) = output {
-        let file = fs::File::open(path)?;
+        let file = fs::File::create(path)?;
         Ok(Box::new(io::BufWriter::new(file)))
     } else {
         Ok(Box::new(io::BufWriter::new(io::stdout())))
     }
 }
 
-fn main() {
+fn extension_format(path: Option<&PathBuf>) -> Option<Format> {
+    path
+        .and_then(|p| p.extension())
+        .and_then(|e| e.to_str())
+        .and_then(Format::from_extension)
+}
+
+/// Sniffs a format from the head of a byte stream, skipping leading blank
+/// and `#`-comment lines: a leading `{`/`[` means JSON, a `key = value` or
+/// `[table]` first line means TOML, anything else is assumed to be YAML.
+fn sniff_format(head: &[u8]) -> Format {
+    let head = String::from_utf8_lossy(head);
+    let first_line = head
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or("");
+
+    // A TOML table header (`[package]`, `[[workspace.members]]`) also starts
+    // with `[`, so it must be ruled out before the blanket JSON check below.
+    // A header is a bracketed line holding only key characters (alphanumerics,
+    // `_`, `-`, `.` and quotes); a JSON array whose first line has a comma,
+    // colon, space or other punctuation is not one. A one-element array with
+    // none of those, such as `[1]` or `["a"]`, is taken for a TOML header.
+    let is_table_header = first_line
+        .strip_prefix("[[")
+        .and_then(|s| s.strip_suffix("]]"))
+        .or_else(|| first_line.strip_prefix('[').and_then(|s| s.strip_suffix(']')))
+        .is_some_and(|inner| {
+            !inner.is_empty()
+                && inner.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '"' | '\''))
+        });
+
+    let looks_like_toml = is_table_header
+        || first_line
+            .split_once('=')
+            .is_some_and(|(key, _)| !key.trim().is_empty() && !key.contains(':'));
+
+    if looks_like_toml {
+        return Format::Toml;
+    }
+
+    if first_line.starts_with('{') || first_line.starts_with('[') {
+        return Format::Json;
+    }
+
+    Format::Yaml
+}
+
+fn resolve_input_format(
+    fmt: Format,
+    path: Option<&PathBuf>,
+    input: &mut Box<dyn io::BufRead>,
+) -> io::Result<Format> {
+    if !matches!(fmt, Format::Auto) {
+        return Ok(fmt);
+    }
+
+    if let Some(f) = extension_format(path) {
+        return Ok(f);
+    }
+
+    Ok(sniff_format(input.fill_buf()?))
+}
+
+fn resolve_output_format(fmt: Format, path: Option<&PathBuf>, mirror: Format) -> Format {
+    if !matches!(fmt, Format::Auto) {
+        return fmt;
+    }
+
+    extension_format(path).unwrap_or(mirror)
+}
+
+/// Runs a selector expression against `content`. A single match round-trips
+/// as the bare matched value (so `--expr name` behaves like plain field
+/// extraction), while zero or multiple matches collect into a `Value::List`.
+fn apply_expr(content: Value, expr: &str) -> Result<Value, DmtError> {
+    let sel = Selector::parse_expr(expr)?;
+    let mut matches: Vec<Value> = sel.try_match(&content).collect();
+
+    Ok(match matches.len() {
+        1 => matches.remove(0),
+        _ => Value::List(matches),
+    })
+}
+
+fn run() -> Result<(), DmtError> {
     let cmd = &Cmd::from_args();
 
-    let mut input = get_input(cmd.input.as_ref()).unwrap();
-    let mut output = get_output(cmd.output.as_ref()).unwrap();
+    let mut input = get_input(cmd.input.as_ref())?;
+    let mut output = get_output(cmd.output.as_ref())?;
+
+    let from = resolve_input_format(cmd.from, cmd.input.as_ref(), &mut input)?;
+    let to = resolve_output_format(cmd.to, cmd.output.as_ref(), from);
 
-    let content: Value = match cmd.from {
-        Format::Auto | Format::Json => serde_json::from_reader(input).unwrap(),
-        Format::Yaml => serde_yaml::from_reader(input).unwrap(),
+    let content: Value = match from {
+        Format::Auto | Format::Json => serde_json::from_reader(input)?,
+        Format::Yaml => serde_yaml::from_reader(input)?,
         Format::Toml => {
             let mut s = String::new();
-            input.read_to_string(&mut s).unwrap();
-            toml::from_str(&s).unwrap()
+            input.read_to_string(&mut s)?;
+            toml::from_str(&s)?
             },
+        Format::Ron => ron::de::from_reader(input)?,
+        Format::Cbor => serde_cbor::from_reader(input)?,
     };
 
-    let content = if let Some(expr) = &cmd.expr {
-        let (s, m) = MatcherChain::parse(&expr).unwrap();
-        if !s.is_empty() {
-            panic!("malformed expr");
-        }
-        m.try_match(&content).unwrap()
-    } else {
-        content
+    let content = match &cmd.expr {
+        Some(expr) => apply_expr(content, expr)?,
+        None => content,
     };
 
-    dbg!(&content);
-
-    match cmd.to {
-        Format::Auto | Format::Json => serde_json::to_writer(output, &content).unwrap(),
-        Format::Yaml => serde_yaml::to_writer(output, &content).unwrap(),
+    match to {
+        Format::Auto | Format::Json => serde_json::to_writer(output, &content)?,
+        Format::Yaml => serde_yaml::to_writer(output, &content)?,
         Format::Toml => {
-            let s = toml::to_string(&content).unwrap();
-            output.write_all(s.as_ref()).unwrap();
+            let s = toml::to_string(&content)?;
+            output.write_all(s.as_ref())?;
             },
+        Format::Ron => {
+            let s = ron::ser::to_string(&content)?;
+            output.write_all(s.as_ref())?;
+            },
+        Format::Cbor => serde_cbor::to_writer(output, &content)?,
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("dmt: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_expr_single_match_stays_bare() {
+        let content = vmap! { "name" => vstr!("Alice") };
+
+        let result = apply_expr(content, "name").unwrap();
+        assert_eq!(result, vstr!("Alice"));
+    }
+
+    #[test]
+    fn apply_expr_multi_match_collects_into_list() {
+        let content = vlist![vint!(1), vint!(2), vint!(3)];
+
+        let result = apply_expr(content, "*").unwrap();
+        assert_eq!(result, vlist![vint!(1), vint!(2), vint!(3)]);
+    }
+
+    #[test]
+    fn apply_expr_no_match_is_an_empty_list() {
+        let content = vmap! { "name" => vstr!("Alice") };
+
+        let result = apply_expr(content, "age").unwrap();
+        assert_eq!(result, Value::List(vec![]));
+    }
+
+    #[test]
+    fn extension_format_recognizes_known_extensions() {
+        assert_eq!(extension_format(Some(&PathBuf::from("a.json"))), Some(Format::Json));
+        assert_eq!(extension_format(Some(&PathBuf::from("a.yml"))), Some(Format::Yaml));
+        assert_eq!(extension_format(Some(&PathBuf::from("a.ron"))), Some(Format::Ron));
+        assert_eq!(extension_format(Some(&PathBuf::from("a.cbor"))), Some(Format::Cbor));
+        assert_eq!(extension_format(Some(&PathBuf::from("a.txt"))), None);
+        assert_eq!(extension_format(None), None);
+    }
+
+    #[test]
+    fn get_output_creates_and_writes_a_file() {
+        let path = std::env::temp_dir().join("dmt_get_output_creates_and_writes_a_file.txt");
+        let _ = fs::remove_file(&path);
+
+        {
+            let mut output = get_output(Some(&path)).unwrap();
+            output.write_all(b"hello").unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cbor_round_trips_a_value() {
+        let content = vmap! { "name" => vstr!("Alice"), "age" => vint!(30) };
+
+        let mut bytes = Vec::new();
+        serde_cbor::to_writer(&mut bytes, &content).unwrap();
+        let decoded: Value = serde_cbor::from_reader(bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded, content);
+    }
+
+    #[test]
+    fn sniff_format_json_array() {
+        assert_eq!(sniff_format(b"[1, 2, 3]"), Format::Json);
+    }
+
+    #[test]
+    fn sniff_format_json_object() {
+        assert_eq!(sniff_format(b"{\"a\": 1}"), Format::Json);
+    }
+
+    #[test]
+    fn sniff_format_toml_table_header_not_mistaken_for_json_array() {
+        assert_eq!(sniff_format(b"[package]\nname = \"dmt\"\n"), Format::Toml);
+    }
+
+    #[test]
+    fn sniff_format_toml_array_of_tables() {
+        assert_eq!(sniff_format(b"[[workspace.members]]\n"), Format::Toml);
+    }
+
+    #[test]
+    fn sniff_format_toml_key_value() {
+        assert_eq!(sniff_format(b"name = \"dmt\"\n"), Format::Toml);
+    }
+
+    #[test]
+    fn sniff_format_toml_leading_comment() {
+        assert_eq!(sniff_format(b"# a comment\nname = \"dmt\"\n"), Format::Toml);
+    }
+
+    #[test]
+    fn sniff_format_yaml_key_value_is_not_toml() {
+        assert_eq!(sniff_format(b"name: dmt\n"), Format::Yaml);
+    }
+
+    #[test]
+    fn sniff_format_empty_input_defaults_to_yaml() {
+        assert_eq!(sniff_format(b""), Format::Yaml);
+    }
+
+    #[test]
+    fn resolve_output_format_prefers_extension_over_mirror() {
+        let fmt = resolve_output_format(Format::Auto, Some(&PathBuf::from("out.ron")), Format::Json);
+        assert_eq!(fmt, Format::Ron);
+    }
+
+    #[test]
+    fn resolve_output_format_falls_back_to_mirror_without_a_path() {
+        let fmt = resolve_output_format(Format::Auto, None, Format::Yaml);
+        assert_eq!(fmt, Format::Yaml);
+    }
+
+    #[test]
+    fn resolve_output_format_passes_through_an_explicit_format() {
+        let fmt = resolve_output_format(Format::Json, Some(&PathBuf::from("out.ron")), Format::Yaml);
+        assert_eq!(fmt, Format::Json);
+    }
+
+    #[test]
+    fn resolve_input_format_prefers_extension_over_sniffing() {
+        let mut input: Box<dyn io::BufRead> = Box::new(io::BufReader::new(&b"name: dmt\n"[..]));
+        let fmt = resolve_input_format(Format::Auto, Some(&PathBuf::from("in.json")), &mut input).unwrap();
+        assert_eq!(fmt, Format::Json);
+    }
+
+    #[test]
+    fn resolve_input_format_sniffs_when_extension_is_unknown() {
+        let mut input: Box<dyn io::BufRead> = Box::new(io::BufReader::new(&b"[package]\nname = \"dmt\"\n"[..]));
+        let fmt = resolve_input_format(Format::Auto, Some(&PathBuf::from("in.txt")), &mut input).unwrap();
+        assert_eq!(fmt, Format::Toml);
     }
 }
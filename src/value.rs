@@ -1,13 +1,14 @@
+use indexmap::IndexMap;
 use serde::{de::{ Visitor, MapAccess, SeqAccess, }, Deserialize, Deserializer, Serialize, Serializer};
-use std::{collections::HashMap, error::Error, fmt};
+use std::{error::Error, fmt};
 
 #[macro_export]
 macro_rules! vmap {
     {$($key: expr => $val: expr), +} => {
         Value::Map({
-            use std::collections::HashMap;
-            let mut m: HashMap<String, Value> = HashMap::new();
-            $(m.insert(($key).to_owned(), $val);),+
+            use indexmap::IndexMap;
+            let mut m: IndexMap<String, Value> = IndexMap::new();
+            $(m.insert(($key).to_owned(), $val);)+
             m
         })
     }
@@ -62,8 +63,12 @@ pub enum Value {
     Float(f64),
     Bool(bool),
     String(String),
-    Map(HashMap<String, Value>),
+    Map(IndexMap<String, Value>),
     List(Vec<Value>),
+    /// A half-open integer range `start..end`, produced by the selector
+    /// layer from a bare range literal (e.g. `2:5`); a bracketed list slice
+    /// yields a `Value::List` instead.
+    Range(i64, i64),
 }
 
 impl Serialize for Value {
@@ -79,17 +84,90 @@ impl Serialize for Value {
             Value::String(x) => serializer.serialize_str(x),
             Value::Map(x) => x.serialize(serializer),
             Value::List(x) => x.serialize(serializer),
+            Value::Range(start, end) => {
+                use serde::ser::SerializeSeq;
+                let mut seq = serializer.serialize_seq(Some(2))?;
+                seq.serialize_element(start)?;
+                seq.serialize_element(end)?;
+                seq.end()
+            }
         }
     }
 }
 
+/// A map key decoded via `deserialize_any`, so RON's unquoted struct field
+/// names resolve alongside quoted string keys and non-string scalar keys
+/// (ints, bools, floats) still stringify as before.
+struct MapKey(String);
+
+impl<'de> Deserialize<'de> for MapKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = MapKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a map key")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(MapKey(v.to_owned()))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(MapKey(v))
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(MapKey(v.to_string()))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(MapKey(v.to_string()))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(MapKey(v.to_string()))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(MapKey(v.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
 struct ValueVisitor {}
 
 impl <'de> Visitor<'de> for ValueVisitor {
     type Value = Value;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        write!(formatter, "hoge")
+        write!(formatter, "a dmt value")
     }
 
     fn visit_unit<E>(self) -> Result<Self::Value, E>
@@ -113,6 +191,34 @@ impl <'de> Visitor<'de> for ValueVisitor {
         Ok(Value::Int(v))
     }
 
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Value::Int(v as i64))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Value::Int(v as i64))
+    }
+
     fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
     where
         E: Error,
@@ -152,10 +258,10 @@ impl <'de> Visitor<'de> for ValueVisitor {
     where
         A: MapAccess<'de>
     {
-        let mut val: HashMap<String, Value> = HashMap::new();
+        let mut val: IndexMap<String, Value> = IndexMap::new();
 
-        while let Some((k, v)) = map.next_entry::<String, Value>()? {
-            val.insert(k, v);
+        while let Some((k, v)) = map.next_entry::<MapKey, Value>()? {
+            val.insert(k.0, v);
         }
 
         Ok(Value::Map(val))
@@ -170,3 +276,78 @@ impl <'de> Deserialize<'de> for Value {
         deserializer.deserialize_any(ValueVisitor{})
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vmap_builds_a_map_with_more_than_one_entry() {
+        let v = vmap! { "name" => vstr!("Alice"), "age" => vint!(30) };
+        let m = match &v {
+            Value::Map(m) => m,
+            _ => panic!("expected a Value::Map"),
+        };
+        let keys: Vec<_> = m.keys().cloned().collect();
+        assert_eq!(keys, vec!["name", "age"]);
+    }
+
+    #[test]
+    fn map_preserves_key_insertion_order_through_a_json_round_trip() {
+        let v: Value = serde_json::from_str(r#"{"z":1,"a":2,"m":3}"#).unwrap();
+        let m = match &v {
+            Value::Map(m) => m,
+            _ => panic!("expected a Value::Map"),
+        };
+        let keys: Vec<_> = m.keys().cloned().collect();
+        assert_eq!(keys, vec!["z", "a", "m"]);
+
+        let out = serde_json::to_string(&v).unwrap();
+        assert_eq!(out, r#"{"z":1,"a":2,"m":3}"#);
+    }
+
+    #[test]
+    fn map_accepts_unquoted_ron_struct_field_names() {
+        let v: Value = ron::de::from_str(r#"(a: "x", b: "y")"#).unwrap();
+        let m = match &v {
+            Value::Map(m) => m,
+            _ => panic!("expected a Value::Map"),
+        };
+        let keys: Vec<_> = m.keys().cloned().collect();
+        assert_eq!(keys, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn non_negative_json_integers_deserialize() {
+        let v: Value = serde_json::from_str("1").unwrap();
+        assert_eq!(v, Value::Int(1));
+    }
+
+    #[test]
+    fn map_accepts_integer_keys_in_yaml() {
+        let v: Value = serde_yaml::from_str("1: foo\n2: bar\n").unwrap();
+        let m = match &v {
+            Value::Map(m) => m,
+            _ => panic!("expected a Value::Map"),
+        };
+        let keys: Vec<_> = m.keys().cloned().collect();
+        assert_eq!(keys, vec!["1", "2"]);
+    }
+
+    #[test]
+    fn range_serializes_as_a_two_element_list() {
+        let out = serde_json::to_string(&Value::Range(2, 5)).unwrap();
+        assert_eq!(out, "[2,5]");
+    }
+
+    #[test]
+    fn map_accepts_integer_keys_in_ron() {
+        let v: Value = ron::de::from_str(r#"{1: "a", 2: "b"}"#).unwrap();
+        let m = match &v {
+            Value::Map(m) => m,
+            _ => panic!("expected a Value::Map"),
+        };
+        let keys: Vec<_> = m.keys().cloned().collect();
+        assert_eq!(keys, vec!["1", "2"]);
+    }
+}